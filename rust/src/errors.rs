@@ -0,0 +1,74 @@
+use bitcoincore_rpc::{jsonrpc, Error};
+
+/// A subset of bitcoind's documented RPC error codes
+/// (see `src/rpc/protocol.h` in bitcoin core) that this crate needs to branch
+/// on. Matching on these instead of `err.to_string().contains("code: -4")`
+/// keeps the wallet bootstrap and retry logic from breaking across bitcoind
+/// versions/locales.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcErrorCode {
+    /// RPC_WALLET_ALREADY_LOADED
+    WalletAlreadyLoaded,
+    /// RPC_WALLET_ERROR
+    WalletError,
+    /// RPC_WALLET_NOT_FOUND
+    WalletNotFound,
+    /// RPC_IN_WARMUP
+    InWarmup,
+    /// Any other numeric code we don't special-case.
+    Other(i32),
+}
+
+impl RpcErrorCode {
+    fn from_code(code: i32) -> Self {
+        match code {
+            -4 => Self::WalletError,
+            -18 => Self::WalletNotFound,
+            -28 => Self::InWarmup,
+            -35 => Self::WalletAlreadyLoaded,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Extracts the numeric RPC error code out of a `bitcoincore_rpc::Error`, if
+/// it wraps one, mapping it to a typed [`RpcErrorCode`].
+pub fn rpc_error_code(err: &Error) -> Option<RpcErrorCode> {
+    match err {
+        Error::JsonRpc(jsonrpc::error::Error::Rpc(rpc_err)) => {
+            Some(RpcErrorCode::from_code(rpc_err.code))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpc_error(code: i32) -> Error {
+        Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+            code,
+            message: "test".to_string(),
+            data: None,
+        }))
+    }
+
+    #[test]
+    fn maps_known_codes_to_their_variant() {
+        assert_eq!(rpc_error_code(&rpc_error(-35)), Some(RpcErrorCode::WalletAlreadyLoaded));
+        assert_eq!(rpc_error_code(&rpc_error(-4)), Some(RpcErrorCode::WalletError));
+        assert_eq!(rpc_error_code(&rpc_error(-18)), Some(RpcErrorCode::WalletNotFound));
+        assert_eq!(rpc_error_code(&rpc_error(-28)), Some(RpcErrorCode::InWarmup));
+    }
+
+    #[test]
+    fn maps_unknown_codes_to_other() {
+        assert_eq!(rpc_error_code(&rpc_error(-999)), Some(RpcErrorCode::Other(-999)));
+    }
+
+    #[test]
+    fn non_rpc_errors_have_no_code() {
+        assert_eq!(rpc_error_code(&Error::ReturnedError("boom".to_string())), None);
+    }
+}
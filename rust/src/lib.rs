@@ -1,5 +1,7 @@
 use bitcoin::hex::{Case, DisplayHex};
-use bitcoincore_rpc::bitcoin::{Address, Amount, BlockHash, Network, Txid};
+use bitcoincore_rpc::bitcoin::{
+    Address, Amount, BlockHash, Denomination, Network, SignedAmount, Txid,
+};
 use bitcoincore_rpc::bitcoincore_rpc_json::AddressType;
 use bitcoincore_rpc::json::LoadWalletResult;
 use bitcoincore_rpc::{Auth, Client, Error, RpcApi};
@@ -7,8 +9,26 @@ use dotenv as env;
 use std::fmt::Display;
 use std::fs::File;
 use std::io::Write;
+use std::time::Duration;
 
-pub fn run_rpc_scenario() -> Result<(), Error> {
+mod errors;
+mod metadata;
+mod network;
+mod rpc_client;
+mod watcher;
+
+pub use errors::{rpc_error_code, RpcErrorCode};
+pub use metadata::{recover_metadata, send_with_metadata};
+pub use network::{detect_network, guard_not_mainnet};
+pub use rpc_client::{ReconnectingClient, RetryPolicy};
+pub use watcher::wait_for_confirmations;
+
+/// Runs the Miner -> Trader scenario end to end. When `metadata` is `Some`,
+/// the payment transaction carries an extra OP_RETURN output tagging the
+/// payload (see [`send_with_metadata`]) instead of a plain send.
+/// `confirmation_target` is how many confirmations to wait for (see
+/// [`wait_for_confirmations`]) before the result is written out.
+pub fn run_rpc_scenario(metadata: Option<&[u8]>, confirmation_target: u32) -> Result<(), Error> {
     let rpc_user = env::var("user").map_err(|_| {
         bitcoincore_rpc::Error::ReturnedError("cannot load username from env file".into())
     })?;
@@ -19,13 +39,15 @@ pub fn run_rpc_scenario() -> Result<(), Error> {
         bitcoincore_rpc::Error::ReturnedError("cannot load rpc-url from env file".into())
     })?;
 
-    // Connect to Bitcoin Core RPC
-    let miner_rpc = Client::new(
+    // Connect to Bitcoin Core RPC. Wrapped in `ReconnectingClient` so a bitcoind
+    // still warming up (`code: -28`) or a dropped localhost socket doesn't abort
+    // the whole scenario.
+    let mut miner_rpc = ReconnectingClient::new(
         format!("{rpc_url}/wallet/Miner").as_str(),
         Auth::UserPass(rpc_user.to_owned(), rpc_password.to_owned()),
     )?;
 
-    let trader_rpc = Client::new(
+    let mut trader_rpc = ReconnectingClient::new(
         format!("{rpc_url}/wallet/Trader").as_str(),
         Auth::UserPass(rpc_user, rpc_password),
     )?;
@@ -34,17 +56,23 @@ pub fn run_rpc_scenario() -> Result<(), Error> {
     let blockchain_info = miner_rpc.get_blockchain_info()?;
     println!("Blockchain Info: {blockchain_info:?}");
 
+    // Detect which network the node is actually on instead of assuming
+    // regtest, and refuse to fund wallets / mine blocks on mainnet. Reuses
+    // the `chain` already fetched above rather than re-querying it.
+    let network = detect_network(&blockchain_info.chain)?;
+    guard_not_mainnet(network)?;
+
     // Create/Load the wallets, named 'Miner' and 'Trader'. Have logic to optionally create/load them if they do not exist or not loaded already.
     let miner_wallet = "Miner";
-    get_wallet(&miner_rpc, miner_wallet)?;
+    miner_rpc.get_wallet(miner_wallet)?;
 
     let trader_wallet = "Trader";
-    get_wallet(&trader_rpc, trader_wallet)?;
+    trader_rpc.get_wallet(trader_wallet)?;
 
     // Generate spendable balances in the Miner wallet. How many blocks needs to be mined?
     let miner_input_address = miner_rpc
         .get_new_address(Some("Mining Reward"), Some(AddressType::Bech32))?
-        .require_network(Network::Regtest)
+        .require_network(network)
         .expect("new miner address");
 
     // generate 101 blocks first to obtain the funds
@@ -60,20 +88,29 @@ pub fn run_rpc_scenario() -> Result<(), Error> {
     // Load Trader wallet and generate a new address
     let trader_output_address = trader_rpc
         .get_new_address(Some("BTC trades"), Some(AddressType::Bech32))?
-        .require_network(Network::Regtest)
+        .require_network(network)
         .map_err(|e| bitcoincore_rpc::Error::ReturnedError(e.to_string()))?;
 
-    // Send 20 BTC from Miner to Trader
-    let tx_id = miner_rpc.send_to_address(
-        &trader_output_address,
-        Amount::from_int_btc(20),
-        Some("I will send you some BTC for trading!"),
-        Some("my friend best trader"),
-        None,
-        None,
-        None,
-        None,
-    )?;
+    // Send 20 BTC from Miner to Trader, optionally tagged with an OP_RETURN
+    // metadata payload.
+    let tx_id = match metadata {
+        Some(payload) => send_with_metadata(
+            &mut miner_rpc,
+            &trader_output_address,
+            Amount::from_int_btc(20),
+            payload,
+        )?,
+        None => miner_rpc.send_to_address(
+            &trader_output_address,
+            Amount::from_int_btc(20),
+            Some("I will send you some BTC for trading!"),
+            Some("my friend best trader"),
+            None,
+            None,
+            None,
+            None,
+        )?,
+    };
 
     // Check transaction in mempool
     let mempool_entry = miner_rpc
@@ -81,32 +118,27 @@ pub fn run_rpc_scenario() -> Result<(), Error> {
         .map_err(|e| bitcoincore_rpc::Error::ReturnedError(e.to_string()))?;
     println!("Mempool Entry: {mempool_entry:?}");
 
-    // Mine 1 block to confirm the transaction
-    let confirmation_block = miner_rpc.generate_to_address(1, &miner_input_address);
+    // Mine 1 block so the transaction gets its first confirmation to start from.
+    miner_rpc.generate_to_address(1, &miner_input_address)?;
 
     let miner_tx = miner_rpc.get_transaction(&tx_id, None)?;
     let miner_tx_details = miner_tx.details;
 
-    // Miner's Input Amount (in BTC)
-    // we need to aggregate all inputs into a total amount (there could be multiple inputs)
-    let miner_input_amount = f64::abs(
-        miner_tx_details
-            .iter()
-            .map(|detail| detail.amount.to_btc())
-            .sum(),
-    );
+    // Miner's Input Amount
+    // we need to aggregate all inputs into a total amount (there could be multiple inputs).
+    // Sum in signed satoshis to stay exact, then take the absolute value as an unsigned Amount.
+    let miner_input_sats: i64 = miner_tx_details.iter().map(|detail| detail.amount.to_sat()).sum();
+    let miner_input_amount = SignedAmount::from_sat(miner_input_sats).unsigned_abs();
 
     // Trader Output Amount
     let trader_tx = trader_rpc.get_transaction(&tx_id, None)?;
     let trader_tx_details = trader_tx.details;
-    let trader_output_amount: f64 = trader_tx_details
-        .iter()
-        .map(|detail| detail.amount.to_btc())
-        .sum();
+    let trader_output_sats: i64 = trader_tx_details.iter().map(|detail| detail.amount.to_sat()).sum();
+    let trader_output_amount = SignedAmount::from_sat(trader_output_sats).unsigned_abs();
 
     // Miner's Change Address
     let miner_raw_tx =
-        miner_rpc.decode_raw_transaction(miner_tx.hex.to_hex_string(Case::Lower), Some(true))?;
+        miner_rpc.decode_raw_transaction(&miner_tx.hex.to_hex_string(Case::Lower), Some(true))?;
     let miner_vout = miner_raw_tx
         .vout
         .iter()
@@ -127,21 +159,25 @@ pub fn run_rpc_scenario() -> Result<(), Error> {
         .ok_or_else(|| {
             bitcoincore_rpc::Error::ReturnedError("No address found in script_pub_key".to_string())
         })?
-        .require_network(Network::Regtest)
+        .require_network(network)
         .map_err(|e| bitcoincore_rpc::Error::ReturnedError(e.to_string()))?;
 
     // Miner Change Amount
-    let miner_change_amount = miner_vout.value.to_btc();
+    let miner_change_amount = miner_vout.value;
 
-    // Transaction Fees (in BTC)
-    let fee = miner_tx.fee.expect("fee miner tx").to_btc();
+    // Transaction Fees
+    let fee = miner_tx.fee.expect("fee miner tx").unsigned_abs();
 
-    // Block height at which the transaction is confirmed
-    // Block hash at which the transaction is confirmed
-    // we pick up the first block hash, because in generate_to_address() we mine 1 block
-    let confirmation_block_hash = *confirmation_block?.first().unwrap();
-    let block_info = miner_rpc.get_block_info(&confirmation_block_hash)?;
-    let block_height = block_info.height as u64;
+    // Wait until the transaction has reached `confirmation_target` confirmations
+    // (mining further blocks on regtest if needed), and record the block that
+    // actually confirmed it, rather than assuming the first block mined did.
+    let (confirmation_block_hash, block_height) = wait_for_confirmations(
+        &mut miner_rpc,
+        &tx_id,
+        confirmation_target,
+        Duration::from_millis(500),
+        (network == Network::Regtest).then_some(&miner_input_address),
+    )?;
 
     // Write the data to ../out.txt in the specified format given in readme.md
     let output = OutputFile {
@@ -175,12 +211,12 @@ fn write_to_file(output: OutputFile) -> Result<(), Error> {
 struct OutputFile {
     txid: Txid,
     miner_input_address: Address,
-    miner_input_amount: f64,
+    miner_input_amount: Amount,
     trader_output_address: Address,
-    trader_output_amount: f64,
+    trader_output_amount: Amount,
     miner_change_address: Address,
-    miner_change_amount: f64,
-    fee: f64,
+    miner_change_amount: Amount,
+    fee: Amount,
     block_height: u64,
     confirmation_block_hash: BlockHash,
 }
@@ -196,46 +232,99 @@ impl OutputFile {
         vec![
             self.txid.to_string(),
             self.miner_input_address.to_string(),
-            self.miner_input_amount.to_string(),
+            self.miner_input_amount.to_string_in(Denomination::Bitcoin),
             self.trader_output_address.to_string(),
-            self.trader_output_amount.to_string(),
+            self.trader_output_amount.to_string_in(Denomination::Bitcoin),
             self.miner_change_address.to_string(),
-            self.miner_change_amount.to_string(),
-            self.fee.to_string(),
+            self.miner_change_amount.to_string_in(Denomination::Bitcoin),
+            self.fee.to_string_in(Denomination::Bitcoin),
             self.block_height.to_string(),
             self.confirmation_block_hash.to_string(),
         ]
     }
 }
 
-fn get_wallet(rpc: &Client, wallet_name: &str) -> bitcoincore_rpc::Result<LoadWalletResult> {
-    // Check if wallet exists
-    let wallets = rpc.list_wallets()?;
-    let wallet_exists = wallets.iter().any(|wallet| wallet == wallet_name);
-
-    if wallet_exists {
-        // Try loading the wallet
-        match rpc.load_wallet(wallet_name) {
-            Ok(result) => Ok(result),
-            Err(e) => {
-                // If error is "already loaded" (code -4), unload and retry
-                if e.to_string().contains("code: -4") {
-                    rpc.unload_wallet(Some(wallet_name))?;
-                    rpc.load_wallet(wallet_name)
-                } else {
-                    Err(e)
-                }
-            }
-        }
-    } else {
-        // Try creating a new wallet
-        rpc.create_wallet(wallet_name, None, None, None, None)
-            .map_err(|e| {
-                if e.to_string().contains("code: -4") {
-                    Error::ReturnedError("Wallet already exists but was not listed".into())
-                } else {
-                    e
-                }
+/// What to do about a `load_wallet` attempt, decided from its result alone so
+/// this branching can be unit tested without a live node.
+enum WalletBootstrap {
+    Use(LoadWalletResult),
+    Create,
+    Propagate(Error),
+}
+
+fn wallet_bootstrap(
+    load_result: bitcoincore_rpc::Result<LoadWalletResult>,
+    wallet_name: &str,
+) -> WalletBootstrap {
+    match load_result {
+        Ok(result) => WalletBootstrap::Use(result),
+        // Already loaded: nothing to do, carry on as if we'd just loaded it.
+        Err(e) if rpc_error_code(&e) == Some(RpcErrorCode::WalletAlreadyLoaded) => {
+            WalletBootstrap::Use(LoadWalletResult {
+                name: wallet_name.to_string(),
+                warning: None,
             })
+        }
+        // Doesn't exist on disk yet: create it instead.
+        Err(e) if rpc_error_code(&e) == Some(RpcErrorCode::WalletNotFound) => {
+            WalletBootstrap::Create
+        }
+        Err(e) => WalletBootstrap::Propagate(e),
+    }
+}
+
+fn get_wallet(rpc: &Client, wallet_name: &str) -> bitcoincore_rpc::Result<LoadWalletResult> {
+    match wallet_bootstrap(rpc.load_wallet(wallet_name), wallet_name) {
+        WalletBootstrap::Use(result) => Ok(result),
+        WalletBootstrap::Create => rpc.create_wallet(wallet_name, None, None, None, None),
+        WalletBootstrap::Propagate(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::jsonrpc;
+
+    fn rpc_error(code: i32) -> Error {
+        Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+            code,
+            message: "test".to_string(),
+            data: None,
+        }))
+    }
+
+    #[test]
+    fn already_loaded_wallet_is_treated_as_loaded() {
+        let bootstrap = wallet_bootstrap(Err(rpc_error(-35)), "Miner");
+        assert!(matches!(
+            bootstrap,
+            WalletBootstrap::Use(LoadWalletResult { name, .. }) if name == "Miner"
+        ));
+    }
+
+    #[test]
+    fn missing_wallet_is_created() {
+        let bootstrap = wallet_bootstrap(Err(rpc_error(-18)), "Miner");
+        assert!(matches!(bootstrap, WalletBootstrap::Create));
+    }
+
+    #[test]
+    fn other_errors_are_propagated() {
+        let bootstrap = wallet_bootstrap(Err(rpc_error(-1)), "Miner");
+        assert!(matches!(bootstrap, WalletBootstrap::Propagate(_)));
+    }
+
+    #[test]
+    fn successful_load_is_used_as_is() {
+        let loaded = LoadWalletResult {
+            name: "Miner".to_string(),
+            warning: Some("eh".to_string()),
+        };
+        let bootstrap = wallet_bootstrap(Ok(loaded), "Miner");
+        assert!(matches!(
+            bootstrap,
+            WalletBootstrap::Use(LoadWalletResult { warning: Some(w), .. }) if w == "eh"
+        ));
     }
 }
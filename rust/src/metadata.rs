@@ -0,0 +1,126 @@
+use std::str::FromStr;
+
+use bitcoin::hex::{Case, DisplayHex};
+use bitcoincore_rpc::bitcoin::blockdata::script::{Instruction, Script};
+use bitcoincore_rpc::bitcoin::{Address, Amount, Txid};
+use bitcoincore_rpc::Error;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::ReconnectingClient;
+
+/// Magic prefix stamped onto every OP_RETURN payload written by this crate,
+/// so a later scan can tell "one of ours" apart from unrelated OP_RETURN
+/// outputs on the same chain.
+const METADATA_PREFIX: &[u8] = b"RCP1";
+
+/// Sends `amount` to `to_address` with an extra OP_RETURN output carrying
+/// `payload` (prefixed with [`METADATA_PREFIX`]), so the payment can later be
+/// recognized and its intent recovered with [`recover_metadata`].
+///
+/// Built from `createrawtransaction`/`fundrawtransaction`/
+/// `signrawtransactionwithwallet` via the generic `rpc.call`, since the typed
+/// `create_raw_transaction` helper has no way to add a `data` output. Takes
+/// `&mut ReconnectingClient` rather than `&Client` so all four round trips
+/// get the same retry/backoff as the rest of the scenario.
+pub fn send_with_metadata(
+    rpc: &mut ReconnectingClient,
+    to_address: &Address,
+    amount: Amount,
+    payload: &[u8],
+) -> bitcoincore_rpc::Result<Txid> {
+    let data: Vec<u8> = METADATA_PREFIX.iter().chain(payload).copied().collect();
+
+    let outputs = json!([
+        { to_address.to_string(): amount.to_btc() },
+        { "data": data.to_hex_string(Case::Lower) },
+    ]);
+    let raw_hex = rpc.call::<String>("createrawtransaction", &[json!([]), outputs])?;
+
+    #[derive(Deserialize)]
+    struct FundRawTransactionResult {
+        hex: String,
+    }
+    let funded =
+        rpc.call::<FundRawTransactionResult>("fundrawtransaction", &[json!(raw_hex)])?;
+
+    #[derive(Deserialize)]
+    struct SignRawTransactionResult {
+        hex: String,
+        complete: bool,
+    }
+    let signed = rpc.call::<SignRawTransactionResult>(
+        "signrawtransactionwithwallet",
+        &[json!(funded.hex)],
+    )?;
+    if !signed.complete {
+        return Err(Error::ReturnedError(
+            "failed to sign metadata transaction".to_string(),
+        ));
+    }
+
+    let txid = rpc.call::<String>("sendrawtransaction", &[json!(signed.hex)])?;
+    Txid::from_str(&txid).map_err(|e| Error::ReturnedError(e.to_string()))
+}
+
+/// Scans `txid`'s outputs for an OP_RETURN carrying [`METADATA_PREFIX`] and,
+/// if found, returns the payload that followed it.
+pub fn recover_metadata(
+    rpc: &mut ReconnectingClient,
+    txid: &Txid,
+) -> bitcoincore_rpc::Result<Option<Vec<u8>>> {
+    let tx = rpc.get_raw_transaction(txid, None)?;
+    Ok(tx
+        .output
+        .iter()
+        .find_map(|output| extract_metadata(&output.script_pubkey)))
+}
+
+/// Pulls a [`METADATA_PREFIX`]-tagged payload out of a single scriptPubKey,
+/// if it's an OP_RETURN carrying one. Split out from [`recover_metadata`] so
+/// the script-parsing logic can be unit tested without a node.
+fn extract_metadata(script_pubkey: &Script) -> Option<Vec<u8>> {
+    if !script_pubkey.is_op_return() {
+        return None;
+    }
+
+    let mut instructions = script_pubkey.instructions();
+    instructions.next(); // OP_RETURN itself
+    let Some(Ok(Instruction::PushBytes(bytes))) = instructions.next() else {
+        return None;
+    };
+
+    bytes.as_bytes().strip_prefix(METADATA_PREFIX).map(<[u8]>::to_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoincore_rpc::bitcoin::blockdata::script::Builder;
+    use bitcoincore_rpc::bitcoin::opcodes::all::OP_RETURN;
+
+    fn op_return_script(data: &[u8]) -> bitcoincore_rpc::bitcoin::ScriptBuf {
+        Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(<&bitcoincore_rpc::bitcoin::script::PushBytes>::try_from(data).unwrap())
+            .into_script()
+    }
+
+    #[test]
+    fn extracts_payload_behind_the_prefix() {
+        let script = op_return_script(b"RCP1hello world");
+        assert_eq!(extract_metadata(&script), Some(b"hello world".to_vec()));
+    }
+
+    #[test]
+    fn ignores_op_return_without_our_prefix() {
+        let script = op_return_script(b"not ours");
+        assert_eq!(extract_metadata(&script), None);
+    }
+
+    #[test]
+    fn ignores_non_op_return_scripts() {
+        let script = Builder::new().push_int(1).into_script();
+        assert_eq!(extract_metadata(&script), None);
+    }
+}
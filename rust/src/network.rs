@@ -0,0 +1,27 @@
+use bitcoincore_rpc::bitcoin::Network;
+use bitcoincore_rpc::Error;
+
+/// Maps bitcoind's `getblockchaininfo().chain` to the matching
+/// `bitcoin::Network`, so addresses are validated against whatever network
+/// the node is actually on instead of being pinned to regtest.
+///
+/// Takes the chain string rather than an RPC handle: `chain` is already
+/// fetched (and retried) as part of the `getblockchaininfo` call every
+/// scenario makes up front, so re-querying it here would both duplicate that
+/// round trip and, if done through a bare `&Client`, silently drop the retry
+/// wrapper's guarantees.
+pub fn detect_network(chain: &str) -> bitcoincore_rpc::Result<Network> {
+    Network::from_core_arg(chain).map_err(|e| Error::ReturnedError(e.to_string()))
+}
+
+/// Refuses to continue on mainnet. This tool funds wallets and mines blocks
+/// to get there, which would spend real money if ever pointed at
+/// `Network::Bitcoin` by mistake.
+pub fn guard_not_mainnet(network: Network) -> bitcoincore_rpc::Result<()> {
+    if network == Network::Bitcoin {
+        return Err(Error::ReturnedError(
+            "refusing to run the wallet-funding/mining scenario on mainnet".to_string(),
+        ));
+    }
+    Ok(())
+}
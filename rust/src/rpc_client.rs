@@ -0,0 +1,279 @@
+use std::ops::Deref;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use bitcoincore_rpc::bitcoin::address::NetworkUnchecked;
+use bitcoincore_rpc::bitcoin::{Address, Amount, BlockHash, Transaction, Txid};
+use bitcoincore_rpc::bitcoincore_rpc_json::AddressType;
+use bitcoincore_rpc::json::{
+    DecodeRawTransactionResult, EstimateMode, GetBlockInfoResult, GetBlockchainInfoResult,
+    GetMempoolEntryResult, GetTransactionResult, GetWalletInfoResult, LoadWalletResult,
+};
+use bitcoincore_rpc::{Auth, Client, Error, RpcApi};
+use serde::de::DeserializeOwned;
+
+use crate::errors::{rpc_error_code, RpcErrorCode};
+use crate::get_wallet;
+
+/// Backoff policy used between reconnect attempts. Exposed as plain fields so
+/// callers (and tests) can dial it down to zero for a fast, deterministic run.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub initial_delay: Duration,
+    pub multiplier: u32,
+    pub max_elapsed: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            multiplier: 2,
+            max_elapsed: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A `bitcoincore_rpc::Client` that reconnects and retries with backoff when
+/// the node is still warming up (`code: -28`) or the connection drops, instead
+/// of letting a single transient failure abort the whole scenario.
+///
+/// Every call `run_rpc_scenario` makes is wrapped below so a transient
+/// failure anywhere in the scenario (not just wallet bootstrap or the send)
+/// gets retried. `Deref` to the inner `Client` is still available for calls
+/// outside that set.
+pub struct ReconnectingClient {
+    client: Client,
+    url: String,
+    auth: Auth,
+    policy: RetryPolicy,
+}
+
+impl ReconnectingClient {
+    pub fn new(url: &str, auth: Auth) -> bitcoincore_rpc::Result<Self> {
+        Self::with_policy(url, auth, RetryPolicy::default())
+    }
+
+    pub fn with_policy(url: &str, auth: Auth, policy: RetryPolicy) -> bitcoincore_rpc::Result<Self> {
+        let client = Client::new(url, auth.clone())?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+            auth,
+            policy,
+        })
+    }
+
+    fn reconnect(&mut self) -> bitcoincore_rpc::Result<()> {
+        self.client = Client::new(&self.url, self.auth.clone())?;
+        Ok(())
+    }
+
+    fn is_retryable(err: &Error) -> bool {
+        match err {
+            Error::JsonRpc(bitcoincore_rpc::jsonrpc::error::Error::Transport(_)) => true,
+            _ => rpc_error_code(err) == Some(RpcErrorCode::InWarmup),
+        }
+    }
+
+    fn with_retry<T>(
+        &mut self,
+        mut op: impl FnMut(&Client) -> bitcoincore_rpc::Result<T>,
+    ) -> bitcoincore_rpc::Result<T> {
+        let start = Instant::now();
+        let mut delay = self.policy.initial_delay;
+        loop {
+            match op(&self.client) {
+                Ok(value) => return Ok(value),
+                Err(err) if Self::is_retryable(&err) && start.elapsed() < self.policy.max_elapsed => {
+                    sleep(delay);
+                    let _ = self.reconnect();
+                    delay *= self.policy.multiplier;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub fn get_wallet(&mut self, wallet_name: &str) -> bitcoincore_rpc::Result<LoadWalletResult> {
+        self.with_retry(|client| get_wallet(client, wallet_name))
+    }
+
+    pub fn generate_to_address(
+        &mut self,
+        nblocks: u64,
+        address: &Address,
+    ) -> bitcoincore_rpc::Result<Vec<BlockHash>> {
+        self.with_retry(|client| client.generate_to_address(nblocks, address))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_to_address(
+        &mut self,
+        address: &Address,
+        amount: Amount,
+        comment: Option<&str>,
+        comment_to: Option<&str>,
+        subtract_fee: Option<bool>,
+        replaceable: Option<bool>,
+        confirmation_target: Option<u32>,
+        estimate_mode: Option<EstimateMode>,
+    ) -> bitcoincore_rpc::Result<Txid> {
+        self.with_retry(|client| {
+            client.send_to_address(
+                address,
+                amount,
+                comment,
+                comment_to,
+                subtract_fee,
+                replaceable,
+                confirmation_target,
+                estimate_mode,
+            )
+        })
+    }
+
+    pub fn get_blockchain_info(&mut self) -> bitcoincore_rpc::Result<GetBlockchainInfoResult> {
+        self.with_retry(|client| client.get_blockchain_info())
+    }
+
+    pub fn get_new_address(
+        &mut self,
+        label: Option<&str>,
+        address_type: Option<AddressType>,
+    ) -> bitcoincore_rpc::Result<Address<NetworkUnchecked>> {
+        self.with_retry(|client| client.get_new_address(label, address_type))
+    }
+
+    pub fn get_wallet_info(&mut self) -> bitcoincore_rpc::Result<GetWalletInfoResult> {
+        self.with_retry(|client| client.get_wallet_info())
+    }
+
+    pub fn get_mempool_entry(&mut self, txid: &Txid) -> bitcoincore_rpc::Result<GetMempoolEntryResult> {
+        self.with_retry(|client| client.get_mempool_entry(txid))
+    }
+
+    pub fn get_transaction(
+        &mut self,
+        txid: &Txid,
+        include_watchonly: Option<bool>,
+    ) -> bitcoincore_rpc::Result<GetTransactionResult> {
+        self.with_retry(|client| client.get_transaction(txid, include_watchonly))
+    }
+
+    pub fn decode_raw_transaction(
+        &mut self,
+        hex: &str,
+        is_witness: Option<bool>,
+    ) -> bitcoincore_rpc::Result<DecodeRawTransactionResult> {
+        self.with_retry(|client| client.decode_raw_transaction(hex, is_witness))
+    }
+
+    pub fn get_block_info(&mut self, block_hash: &BlockHash) -> bitcoincore_rpc::Result<GetBlockInfoResult> {
+        self.with_retry(|client| client.get_block_info(block_hash))
+    }
+
+    /// Retried escape hatch for RPCs with no typed wrapper, e.g. building a
+    /// raw transaction with a `data` output (see [`crate::send_with_metadata`]).
+    pub fn call<T: DeserializeOwned>(
+        &mut self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> bitcoincore_rpc::Result<T> {
+        self.with_retry(|client| client.call(cmd, args))
+    }
+
+    pub fn get_raw_transaction(
+        &mut self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> bitcoincore_rpc::Result<Transaction> {
+        self.with_retry(|client| client.get_raw_transaction(txid, block_hash))
+    }
+}
+
+impl Deref for ReconnectingClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warmup_error() -> Error {
+        Error::JsonRpc(bitcoincore_rpc::jsonrpc::error::Error::Rpc(
+            bitcoincore_rpc::jsonrpc::error::RpcError {
+                code: -28,
+                message: "Loading block index...".to_string(),
+                data: None,
+            },
+        ))
+    }
+
+    fn zero_delay_client() -> ReconnectingClient {
+        ReconnectingClient::with_policy(
+            "http://127.0.0.1:0",
+            Auth::None,
+            RetryPolicy {
+                initial_delay: Duration::ZERO,
+                multiplier: 1,
+                max_elapsed: Duration::from_secs(5),
+            },
+        )
+        .expect("constructing a client doesn't connect eagerly")
+    }
+
+    #[test]
+    fn with_retry_retries_past_warmup_then_succeeds() {
+        let mut client = zero_delay_client();
+
+        let mut attempts = 0;
+        let result = client.with_retry(|_client| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(warmup_error())
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn with_retry_gives_up_once_max_elapsed_passes() {
+        let mut client = zero_delay_client();
+        client.policy.max_elapsed = Duration::ZERO;
+
+        let mut attempts = 0;
+        let result: bitcoincore_rpc::Result<()> = client.with_retry(|_client| {
+            attempts += 1;
+            // Make sure a non-zero amount of time actually passes so the
+            // `start.elapsed() < max_elapsed` check deterministically fails.
+            sleep(Duration::from_millis(1));
+            Err(warmup_error())
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn non_retryable_error_is_returned_immediately() {
+        let mut client = zero_delay_client();
+
+        let mut attempts = 0;
+        let result: bitcoincore_rpc::Result<()> = client.with_retry(|_client| {
+            attempts += 1;
+            Err(Error::ReturnedError("boom".to_string()))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
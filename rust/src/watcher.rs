@@ -0,0 +1,158 @@
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
+
+use bitcoincore_rpc::bitcoin::{Address, BlockHash, Txid};
+
+use crate::ReconnectingClient;
+
+/// What [`wait_for_confirmations`] needs from an RPC client, abstracted so
+/// the confirmation-decision logic can be unit tested without a live node.
+pub trait ConfirmationRpc {
+    /// `(blockhash, confirmations)` for `txid`, matching `get_transaction`'s
+    /// `info` fields.
+    fn transaction_status(&mut self, txid: &Txid) -> bitcoincore_rpc::Result<(Option<BlockHash>, i32)>;
+    fn block_height(&mut self, block_hash: &BlockHash) -> bitcoincore_rpc::Result<u64>;
+    fn mine(&mut self, address: &Address) -> bitcoincore_rpc::Result<()>;
+}
+
+impl ConfirmationRpc for ReconnectingClient {
+    fn transaction_status(&mut self, txid: &Txid) -> bitcoincore_rpc::Result<(Option<BlockHash>, i32)> {
+        let tx = self.get_transaction(txid, None)?;
+        Ok((tx.info.blockhash, tx.info.confirmations))
+    }
+
+    fn block_height(&mut self, block_hash: &BlockHash) -> bitcoincore_rpc::Result<u64> {
+        Ok(self.get_block_info(block_hash)?.height as u64)
+    }
+
+    fn mine(&mut self, address: &Address) -> bitcoincore_rpc::Result<()> {
+        self.generate_to_address(1, address)?;
+        Ok(())
+    }
+}
+
+/// Polls for `txid`'s confirmation status until it has at least `target`
+/// confirmations, sleeping `poll` between checks, and returns the block that
+/// confirmed it. A mempool transaction reports `confirmations: 0` with no
+/// blockhash, so even `target == 0` waits for it to land in a block — this
+/// function always hands back a real confirming block, never "confirmed by
+/// definition while still unconfirmed."
+///
+/// `rpc` is also used to mine: when `mine_to` is `Some(address)` (regtest
+/// only — nothing else will mine new blocks on its own) a block is mined to
+/// `address` after every unsatisfied check, so the scenario doesn't sit idle
+/// waiting for a network that never produces new blocks.
+pub fn wait_for_confirmations<R: ConfirmationRpc>(
+    rpc: &mut R,
+    txid: &Txid,
+    target: u32,
+    poll: Duration,
+    mine_to: Option<&Address>,
+) -> bitcoincore_rpc::Result<(BlockHash, u64)> {
+    loop {
+        let (blockhash, confirmations) = rpc.transaction_status(txid)?;
+        if let Some(block_hash) = blockhash {
+            if confirmations >= target as i32 {
+                let height = rpc.block_height(&block_hash)?;
+                return Ok((block_hash, height));
+            }
+        }
+
+        if let Some(address) = mine_to {
+            rpc.mine(address)?;
+        }
+
+        sleep(poll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockRpc {
+        statuses: std::vec::IntoIter<(Option<BlockHash>, i32)>,
+        mined: u32,
+    }
+
+    impl MockRpc {
+        fn new(statuses: Vec<(Option<BlockHash>, i32)>) -> Self {
+            Self {
+                statuses: statuses.into_iter(),
+                mined: 0,
+            }
+        }
+    }
+
+    impl ConfirmationRpc for MockRpc {
+        fn transaction_status(&mut self, _txid: &Txid) -> bitcoincore_rpc::Result<(Option<BlockHash>, i32)> {
+            Ok(self.statuses.next().expect("ran out of canned statuses"))
+        }
+
+        fn block_height(&mut self, _block_hash: &BlockHash) -> bitcoincore_rpc::Result<u64> {
+            Ok(42)
+        }
+
+        fn mine(&mut self, _address: &Address) -> bitcoincore_rpc::Result<()> {
+            self.mined += 1;
+            Ok(())
+        }
+    }
+
+    fn some_txid() -> Txid {
+        Txid::from_str(&"22".repeat(32)).unwrap()
+    }
+
+    fn some_block_hash() -> BlockHash {
+        BlockHash::from_str(&"11".repeat(32)).unwrap()
+    }
+
+    #[test]
+    fn returns_immediately_once_confirmed() {
+        let hash = some_block_hash();
+        let mut rpc = MockRpc::new(vec![(Some(hash), 1)]);
+
+        let result = wait_for_confirmations(&mut rpc, &some_txid(), 1, Duration::ZERO, None);
+
+        assert_eq!(result.unwrap(), (hash, 42));
+    }
+
+    #[test]
+    fn mempool_tx_does_not_satisfy_a_zero_confirmation_target() {
+        // Regression test: a mempool transaction reports `confirmations: 0`
+        // with no blockhash, which must not satisfy `target == 0` just
+        // because `0 >= 0`.
+        let hash = some_block_hash();
+        let mut rpc = MockRpc::new(vec![(None, 0), (Some(hash), 1)]);
+
+        let result = wait_for_confirmations(&mut rpc, &some_txid(), 0, Duration::ZERO, None);
+
+        assert_eq!(result.unwrap(), (hash, 42));
+    }
+
+    #[test]
+    fn keeps_polling_until_the_target_is_reached() {
+        let hash = some_block_hash();
+        let mut rpc = MockRpc::new(vec![(Some(hash), 0), (Some(hash), 1), (Some(hash), 2)]);
+
+        let result = wait_for_confirmations(&mut rpc, &some_txid(), 2, Duration::ZERO, None);
+
+        assert_eq!(result.unwrap(), (hash, 42));
+    }
+
+    #[test]
+    fn mines_while_waiting_when_a_mining_address_is_given() {
+        let hash = some_block_hash();
+        let mut rpc = MockRpc::new(vec![(None, 0), (Some(hash), 1)]);
+        let address = Address::p2wsh(
+            bitcoincore_rpc::bitcoin::Script::empty(),
+            bitcoincore_rpc::bitcoin::Network::Regtest,
+        );
+
+        let result = wait_for_confirmations(&mut rpc, &some_txid(), 1, Duration::ZERO, Some(&address));
+
+        assert_eq!(result.unwrap(), (hash, 42));
+        assert_eq!(rpc.mined, 1);
+    }
+}